@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+lazy_static::lazy_static! {
+    pub static ref CONFIG: Arc<Mutex<Config>> = {
+        Arc::new(Mutex::new(Config::default()))
+    };
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    pub wallet: WalletConfig,
+    pub schedule: ScheduleConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WalletConfig {
+    pub address: Vec<String>,
+}
+
+// 部署/心跳超时、轮询间隔与显卡数量筛选，均可在不重新编译的情况下调整
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    pub deploy_timeout_secs: u64,
+    pub heartbeat_timeout_secs: u64,
+    pub poll_interval_secs: u64,
+    pub required_card_number: u32,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            deploy_timeout_secs: 15 * 60,
+            heartbeat_timeout_secs: 10 * 60,
+            poll_interval_secs: 5 * 60,
+            required_card_number: 2,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+mod config;
+mod server;
+
+#[tokio::main]
+async fn main() {
+    let control = tokio::spawn(server::control::serve(std::net::SocketAddr::from((
+        [0, 0, 0, 0],
+        8080,
+    ))));
+    server::address::pool().await;
+    let _ = control.await;
+}
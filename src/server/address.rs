@@ -1,20 +1,46 @@
 use chrono::{DateTime, Local};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
 use strum::Display;
-use tokio::sync::Mutex;
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
 use tracing::{error, info, warn};
 
 use crate::{config::CONFIG, server::clore::Clore};
 
 use super::ssh;
 
+// 钱包状态落盘文件，重启后据此恢复部署绑定关系
+const WALLETS_STATE_FILE: &str = "data/wallets_state.json";
+
+// 实际使用的落盘路径，测试可通过 WALLETS_STATE_FILE_OVERRIDE 环境变量指向临时文件，
+// 避免把测试数据写进生产用的 data/wallets_state.json
+fn wallets_state_path() -> String {
+    std::env::var("WALLETS_STATE_FILE_OVERRIDE").unwrap_or_else(|_| WALLETS_STATE_FILE.to_string())
+}
+
+// 每轮 pool() 循环最多撮合下单的笔数，防止市场行情突增时一次性耗尽待分配钱包
+const MAX_ASSIGNMENTS_PER_TICK: usize = 5;
+
+// curl 重试默认次数及首次退避时长，失败会按 2^n 指数增长再叠加抖动
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+const RETRY_BASE_BACKOFF_MS: u64 = 500;
+const RETRY_JITTER_MS: u64 = 250;
+
+// 区分网络层面的瞬时失败（应重试）和一次成功请求得到的明确否定结果
+#[derive(Debug, Clone)]
+pub enum CurlError {
+    Transient(String),
+}
+
 lazy_static::lazy_static! {
     pub static ref WALLETS_STATE:Arc<Mutex<Address>> = {
         Arc::new(Mutex::new(Address::default()))
     };
 }
 
+// 变体名沿用钱包角色/部署状态的既有大写约定，也是落盘 JSON 的既有字段取值，不做改名
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Display, PartialEq, Clone, Serialize, Deserialize)]
 pub enum AddressType {
     MASTER,
@@ -31,6 +57,8 @@ pub struct Wallet {
     pub deploy: Deployed,
 }
 
+// 同上，沿用既有大写变体名，它们也是落盘 JSON 的既有字段取值
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Deployed {
     NOTASSIGNED,
@@ -48,6 +76,14 @@ pub enum Deployed {
     },
 }
 
+// update_log_collect_time 的结果：区分“确实记录了一次心跳”和“钱包存在但当前不是 DEPLOYING，未发生状态变化”
+#[derive(Debug, PartialEq, Clone)]
+pub enum HeartbeatOutcome {
+    Recorded,
+    NotDeploying,
+    NotFound,
+}
+
 impl Wallet {
     pub fn new(address: String, addr_type: AddressType) -> Wallet {
         Wallet {
@@ -60,7 +96,7 @@ impl Wallet {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Address(pub HashMap<String, Wallet>);
 
 impl std::ops::DerefMut for Address {
@@ -77,42 +113,106 @@ impl std::ops::Deref for Address {
     }
 }
 
-impl Default for Address {
-    fn default() -> Self {
-        Self(Default::default())
+impl Address {
+    // 启动时加载钱包状态文件，文件不存在或解析失败时回退到空状态
+    pub async fn load(path: &str) -> Self {
+        match fs::read_to_string(path).await {
+            Ok(content) => match serde_json::from_str::<Address>(&content) {
+                Ok(address) => {
+                    info!("钱包状态文件加载成功:{}", path);
+                    address
+                }
+                Err(e) => {
+                    error!("钱包状态文件解析失败:{:?},使用空状态", e);
+                    Address::default()
+                }
+            },
+            Err(_) => {
+                warn!("钱包状态文件不存在,使用空状态:{}", path);
+                Address::default()
+            }
+        }
     }
-}
 
-impl Address {
-    async fn mstaddress(address: &str) -> AddressType {
-        let url = "https://mainnet.nimble.technology/check_balance";
-        let result = Address::curl(url, address).await;
-        if let Err(_) = result {
-            return AddressType::NULL;
+    // 原子写入：先写临时文件并 fsync，再 rename 覆盖正式文件，避免写入过程中崩溃导致状态损坏
+    pub async fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
+            }
         }
-        let text = result.unwrap();
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let tmp_path = format!("{}.tmp", path);
+        let mut file = fs::File::create(&tmp_path).await.map_err(|e| e.to_string())?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        file.sync_all().await.map_err(|e| e.to_string())?;
+        fs::rename(&tmp_path, path).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // 在每次可能改变绑定信息的操作之后调用，保证磁盘状态与内存状态一致
+    async fn persist(&self) {
+        if let Err(e) = self.save(&wallets_state_path()).await {
+            error!("钱包状态保存失败:{:?}", e);
+        }
+    }
+
+    async fn mstaddress(address: &str) -> Result<AddressType, CurlError> {
+        let url = "https://mainnet.nimble.technology/check_balance";
+        let text = Address::curl(url, address).await?;
         if text.contains("Error") {
-            AddressType::NULL
+            Ok(AddressType::NULL)
         } else {
-            AddressType::MASTER
+            Ok(AddressType::MASTER)
         }
     }
 
-    async fn subaddress(address: &str) -> AddressType {
+    async fn subaddress(address: &str) -> Result<AddressType, CurlError> {
         let url = "https://mainnet.nimble.technology/register_particle";
-        let result = Address::curl(url, address).await;
-        if let Err(_) = result {
-            return AddressType::NULL;
-        }
-        let text = result.unwrap();
+        let text = Address::curl(url, address).await?;
         if text.contains("Task registered successfully") {
-            AddressType::SUB
+            Ok(AddressType::SUB)
         } else {
-            AddressType::NULL
+            Ok(AddressType::NULL)
         }
     }
 
-    async fn curl(url: &str, address: &str) -> Result<String, String> {
+    // 带指数退避的重试封装；只有耗尽重试次数的传输层失败才会冒泡成 CurlError::Transient
+    async fn curl(url: &str, address: &str) -> Result<String, CurlError> {
+        Address::curl_with_retry(url, address, DEFAULT_RETRY_ATTEMPTS).await
+    }
+
+    async fn curl_with_retry(url: &str, address: &str, attempts: u32) -> Result<String, CurlError> {
+        let mut last_err = String::new();
+        for attempt in 0..attempts.max(1) {
+            match Address::curl_once(url, address).await {
+                Ok(text) => return Ok(text),
+                Err(msg) => {
+                    warn!(
+                        "网络请求第{}/{}次尝试失败:{},{}",
+                        attempt + 1,
+                        attempts,
+                        url,
+                        msg
+                    );
+                    last_err = msg;
+                    if attempt + 1 < attempts {
+                        let backoff_ms = RETRY_BASE_BACKOFF_MS * 2u64.pow(attempt);
+                        let jitter_ms = rand::thread_rng().gen_range(0..RETRY_JITTER_MS);
+                        tokio::time::sleep(std::time::Duration::from_millis(
+                            backoff_ms + jitter_ms,
+                        ))
+                        .await;
+                    }
+                }
+            }
+        }
+        Err(CurlError::Transient(last_err))
+    }
+
+    async fn curl_once(url: &str, address: &str) -> Result<String, String> {
         info!("网络请求:{},{}", url, address);
         let mut params = HashMap::new();
         params.insert("address", address);
@@ -153,7 +253,7 @@ impl Address {
             .collect::<Vec<Wallet>>()
     }
 
-    pub async fn check(&mut self, other_wallets: &Vec<Wallet>) {
+    pub async fn check(&mut self, other_wallets: &[Wallet]) {
         for wallet in other_wallets.iter() {
             let address = wallet.address.clone();
             if (*self).contains_key(&address) {
@@ -167,6 +267,19 @@ impl Address {
 
             let (subaddress, mstaddress) =
                 tokio::join!(Address::subaddress(&address), Address::mstaddress(&address));
+
+            // 任一接口发生瞬时网络错误时跳过该地址，留到下一轮再检测，避免把它错误地判成 NULL
+            if let Err(CurlError::Transient(msg)) = &mstaddress {
+                warn!("地址:{:?}检测时主地址接口瞬时失败,下轮重试:{}", &address, msg);
+                continue;
+            }
+            if let Err(CurlError::Transient(msg)) = &subaddress {
+                warn!("地址:{:?}检测时子地址接口瞬时失败,下轮重试:{}", &address, msg);
+                continue;
+            }
+            let mstaddress = mstaddress.unwrap();
+            let subaddress = subaddress.unwrap();
+
             info!("地址检测结果:{:?},{:?}", mstaddress, subaddress);
             let addr_type = if let AddressType::MASTER = mstaddress {
                 AddressType::MASTER
@@ -183,6 +296,7 @@ impl Address {
             }
             info!("地址匹配结果:{:?}", addr_type.clone());
         }
+        self.persist().await;
     }
 
     // 过滤规则
@@ -227,18 +341,19 @@ impl Address {
         if Deployed::NOTASSIGNED == wallet.deploy {
             wallet.deploy = deploy;
             wallet.start_time = Some(local_time);
+            self.persist().await;
             Ok(())
         } else {
             Err("当前地址状态不是待分配状态！".to_string())
         }
     }
 
-    pub async fn update_log_collect_time(&mut self, wallet_adress: &str) -> bool {
+    pub async fn update_log_collect_time(&mut self, wallet_adress: &str) -> HeartbeatOutcome {
         if !(*self).contains_key(wallet_adress) {
-            return false;
+            return HeartbeatOutcome::NotFound;
         }
         let wallet = (*self).get_mut(wallet_adress).unwrap();
-        if let Deployed::DEPLOYING {
+        let recorded = if let Deployed::DEPLOYING {
             orderid,
             serverid,
             sshaddr,
@@ -248,78 +363,186 @@ impl Address {
             let local_time = Local::now();
             wallet.report_last_time = Some(local_time);
             wallet.deploy = Deployed::DEPLOYED {
-                orderid: orderid.clone(),
-                serverid:serverid.clone(),
+                orderid: *orderid,
+                serverid: *serverid,
                 sshaddr: sshaddr.clone(),
-                sshport: sshport.clone(),
+                sshport: *sshport,
             };
-        }
+            true
+        } else {
+            false
+        };
 
-        true
+        if recorded {
+            self.persist().await;
+            HeartbeatOutcome::Recorded
+        } else {
+            HeartbeatOutcome::NotDeploying
+        }
     }
 
     // 超时未上报时间，则取消该机器订单号，重置所有钱包信息
     pub async fn filter_log_timeout(&mut self, clore: &Clore) {
+        let mutex_conf = Arc::clone(&CONFIG);
+        let (deploy_timeout_secs, heartbeat_timeout_secs) = {
+            let config = mutex_conf.lock().await;
+            (
+                config.schedule.deploy_timeout_secs,
+                config.schedule.heartbeat_timeout_secs,
+            )
+        };
+
         let mut order_ids: Vec<u32> = Vec::new();
         for (_, wallet) in (*self).iter_mut() {
             let nowtime = Local::now();
             match &wallet.deploy {
                 Deployed::NOTASSIGNED => {}
                 Deployed::DEPLOYING { orderid, .. } => {
-                    // 创建时间超过15分钟，还未有上报时间则，进行取消订单
+                    // 创建时间超过部署超时配置，还未有上报时间则，进行取消订单
                     if let Some(start_time) = wallet.start_time {
-                        if nowtime.timestamp() - start_time.timestamp() > 15 * 60 {
-                            order_ids.push(orderid.clone());
+                        if nowtime.timestamp() - start_time.timestamp() > deploy_timeout_secs as i64
+                        {
+                            order_ids.push(*orderid);
                         }
                     }
                 }
                 Deployed::DEPLOYED { orderid, .. } => {
-                    // 上报时间若是超过了十分钟，则也取消，订单号
+                    // 上报时间若是超过了心跳超时配置，则也取消订单号
                     if let Some(report_last_time) = wallet.report_last_time {
-                        if nowtime.timestamp() - report_last_time.timestamp() > 10 * 60 {
-                            order_ids.push(orderid.clone());
+                        if nowtime.timestamp() - report_last_time.timestamp()
+                            > heartbeat_timeout_secs as i64
+                        {
+                            order_ids.push(*orderid);
                         }
                     }
                 }
             }
         }
         for order_id in order_ids.iter() {
-            let result = clore.cancel_order(order_id.clone()).await;
+            let result = clore.cancel_order(*order_id).await;
             if let Err(e) = result {
                 error!("订单:{:?}取消失败,错误码：{:?}", order_id, e);
             } else {
                 warn!("已取消{:?}该订单", order_id);
             }
         }
+        if !order_ids.is_empty() {
+            self.persist().await;
+        }
     }
 }
 
 pub async fn pool() {
-    loop {
+    {
         let wallets = Arc::clone(&WALLETS_STATE);
         let mut locked = wallets.lock().await;
+        *locked = Address::load(&wallets_state_path()).await;
+    }
+
+    loop {
+        let wallets_state = Arc::clone(&WALLETS_STATE);
+
+        // 只在真正读写钱包状态时才持锁；marketplace/下单/取消等网络 I/O 全部在锁外完成，
+        // 这样控制面（/wallets、/assign、/heartbeat、/cancel）不会被一整轮撮合卡住
         let other = Address::load_address_file().await;
-        locked.check(&other).await;
-        let wallets = locked.filter().await;
-        info!("当前绑定信息:{:?}", *locked);
-        // let address = wallets
-        //     .iter()
-        //     .map(|wallet| wallet.address.to_string())
-        //     .collect::<Vec<String>>();
-
-        if wallets.len() > 0 {
-            // warn!("待分配地址:\n{}", address.join("\n"));
-            // let market = Clore::default().marketplace().await;
-            // if let Ok(cards) = market {
-            //     let server_ids = cards
-            //         .iter()
-            //         .filter(|item| item.card_number == 2)
-            //         .map(|item| item.server_id)
-            //         .collect::<Vec<u32>>();
-            //     info!("server_ids:{:?}", server_ids);
-            // }
+        let pending = {
+            let mut locked = wallets_state.lock().await;
+            locked.check(&other).await;
+            let pending = locked.filter().await;
+            info!("当前绑定信息:{:?}", *locked);
+            pending
+        };
+
+        let address = pending
+            .iter()
+            .map(|wallet| wallet.address.to_string())
+            .collect::<Vec<String>>();
+
+        if !pending.is_empty() {
+            warn!("待分配地址:\n{}", address.join("\n"));
+
+            let required_card_number = {
+                let mutex_conf = Arc::clone(&CONFIG);
+                let config = mutex_conf.lock().await;
+                config.schedule.required_card_number
+            };
+
+            match Clore::default().marketplace().await {
+                Ok(cards) => {
+                    let mut server_ids = cards
+                        .iter()
+                        .filter(|item| item.card_number == required_card_number)
+                        .map(|item| item.server_id)
+                        .collect::<Vec<u32>>();
+                    info!("server_ids:{:?}", server_ids);
+
+                    // 每轮最多撮合这么多笔订单，避免市场行情突增时一次性吃光待分配钱包，
+                    // 同时永远不会比待分配钱包数下更多单
+                    let assign_count = pending
+                        .len()
+                        .min(server_ids.len())
+                        .min(MAX_ASSIGNMENTS_PER_TICK);
+                    server_ids.truncate(assign_count);
+
+                    for (wallet, server_id) in pending.iter().zip(server_ids) {
+                        match Clore::default().create_order(server_id).await {
+                            Ok(orderid) => {
+                                let deploy = Deployed::DEPLOYING {
+                                    orderid,
+                                    serverid: server_id,
+                                    sshaddr: None,
+                                    sshport: None,
+                                };
+                                // 只在写入这一条绑定时短暂持锁；下单/取消等网络请求都已经在锁外完成
+                                let assign_result = {
+                                    let mut locked = wallets_state.lock().await;
+                                    locked.assgin_server(&wallet.address, deploy).await
+                                };
+                                // 下单成功后才把钱包标记为分配中；绑定失败说明订单已经是孤儿订单，
+                                // 立刻回滚取消，避免留下一笔既未绑定钱包、又不在 watchdog 监控范围内的订单
+                                if let Err(e) = assign_result {
+                                    error!(
+                                        "钱包:{:?}绑定服务器:{}失败,订单:{},回滚取消该订单:{:?}",
+                                        wallet.address, server_id, orderid, e
+                                    );
+                                    if let Err(cancel_err) =
+                                        Clore::default().cancel_order(orderid).await
+                                    {
+                                        error!(
+                                            "订单:{}回滚取消失败,需人工核实:{:?}",
+                                            orderid, cancel_err
+                                        );
+                                    }
+                                } else {
+                                    info!(
+                                        "钱包:{:?}已绑定服务器:{},订单:{}",
+                                        wallet.address, server_id, orderid
+                                    );
+                                }
+                            }
+                            Err(e) => {
+                                warn!("服务器:{}下单失败,跳过:{:?}", server_id, e);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("获取市场行情失败:{:?}", e);
+                }
+            }
         }
-        drop(locked);
-        tokio::time::sleep(std::time::Duration::from_secs(60 * 5)).await;
+
+        // 回收超时未上报的订单，让自动撮合出来的绑定真正进入 watchdog 监控范围
+        {
+            let mut locked = wallets_state.lock().await;
+            locked.filter_log_timeout(&Clore::default()).await;
+        }
+
+        let poll_interval_secs = {
+            let mutex_conf = Arc::clone(&CONFIG);
+            let config = mutex_conf.lock().await;
+            config.schedule.poll_interval_secs
+        };
+        tokio::time::sleep(std::time::Duration::from_secs(poll_interval_secs)).await;
     }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+// Clore 市场 API 的最小客户端：查询自己的订单、查询市场行情、下单、取消订单
+#[derive(Debug, Default, Clone)]
+pub struct Clore {
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub orderid: u32,
+    pub serverid: u32,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Card {
+    pub server_id: u32,
+    pub card_number: u32,
+}
+
+impl Clore {
+    const BASE_URL: &'static str = "https://api.clore.ai";
+
+    pub async fn my_orders(&self) -> Result<Vec<Order>, String> {
+        let url = format!("{}/my_orders", Self::BASE_URL);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        response.json::<Vec<Order>>().await.map_err(|e| e.to_string())
+    }
+
+    pub async fn marketplace(&self) -> Result<Vec<Card>, String> {
+        let url = format!("{}/marketplace", Self::BASE_URL);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        response.json::<Vec<Card>>().await.map_err(|e| e.to_string())
+    }
+
+    // 下单，成功返回新订单的 orderid
+    pub async fn create_order(&self, server_id: u32) -> Result<u32, String> {
+        let url = format!("{}/order", Self::BASE_URL);
+        let mut params = std::collections::HashMap::new();
+        params.insert("server_id", server_id);
+        info!("发起下单请求:server_id={}", server_id);
+        let response = self
+            .client
+            .post(&url)
+            .json(&params)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let order: Order = response.json().await.map_err(|e| e.to_string())?;
+        Ok(order.orderid)
+    }
+
+    pub async fn cancel_order(&self, orderid: u32) -> Result<(), String> {
+        let url = format!("{}/order/{}/cancel", Self::BASE_URL, orderid);
+        warn!("发起取消订单请求:orderid={}", orderid);
+        self.client
+            .post(&url)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,267 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use super::{
+    address::{Address, Deployed, HeartbeatOutcome, WALLETS_STATE},
+    clore::Clore,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRequest {
+    pub address: String,
+    pub deploy: Deployed,
+}
+
+// 控制面 HTTP 服务，与 pool() 并行运行，用于观测 WALLETS_STATE 并手动干预钱包绑定
+pub async fn serve(addr: SocketAddr) {
+    let app = router();
+    info!("控制服务监听:{}", addr);
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("控制服务绑定端口失败:{:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("控制服务异常退出:{:?}", e);
+    }
+}
+
+fn router() -> Router {
+    Router::new()
+        .route("/wallets", get(get_wallets))
+        .route("/assign", post(post_assign))
+        .route("/cancel/:orderid", post(post_cancel))
+        .route("/heartbeat/:address", post(post_heartbeat))
+}
+
+async fn get_wallets() -> Json<Address> {
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let locked = wallets.lock().await;
+    Json(Address(locked.0.clone()))
+}
+
+async fn post_assign(Json(req): Json<AssignRequest>) -> Result<StatusCode, (StatusCode, String)> {
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    locked
+        .assgin_server(&req.address, req.deploy)
+        .await
+        .map(|_| StatusCode::OK)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+async fn post_cancel(Path(orderid): Path<u32>) -> Result<StatusCode, (StatusCode, String)> {
+    Clore::default()
+        .cancel_order(orderid)
+        .await
+        .map(|_| StatusCode::OK)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{:?}", e)))
+}
+
+async fn post_heartbeat(Path(address): Path<String>) -> StatusCode {
+    let wallets = Arc::clone(&WALLETS_STATE);
+    let mut locked = wallets.lock().await;
+    match locked.update_log_collect_time(&address).await {
+        HeartbeatOutcome::Recorded => StatusCode::OK,
+        // 钱包存在但不是 DEPLOYING，没有发生任何状态变化，与"记录成功"区分开
+        HeartbeatOutcome::NotDeploying => StatusCode::CONFLICT,
+        HeartbeatOutcome::NotFound => StatusCode::NOT_FOUND,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::address::{AddressType, Deployed as WalletDeployed, Wallet};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    lazy_static::lazy_static! {
+        // 测试共享同一个全局 WALLETS_STATE 和落盘路径覆盖环境变量，串行执行避免互相踩踏
+        static ref TEST_GUARD: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+    }
+
+    // 持有测试互斥锁、将落盘路径指向临时文件，析构时清理，不碰生产用的 data/wallets_state.json
+    struct TestState {
+        _guard: tokio::sync::MutexGuard<'static, ()>,
+        state_path: std::path::PathBuf,
+    }
+
+    impl Drop for TestState {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.state_path);
+            let _ = std::fs::remove_file(format!("{}.tmp", self.state_path.display()));
+        }
+    }
+
+    async fn setup(name: &str) -> TestState {
+        let guard = TEST_GUARD.lock().await;
+        let state_path = std::env::temp_dir().join(format!("clore-control-test-{}.json", name));
+        std::env::set_var("WALLETS_STATE_FILE_OVERRIDE", &state_path);
+
+        let wallets = Arc::clone(&WALLETS_STATE);
+        let mut locked = wallets.lock().await;
+        locked.clear();
+        drop(locked);
+
+        TestState {
+            _guard: guard,
+            state_path,
+        }
+    }
+
+    async fn seed(address: &str, addr_type: AddressType) {
+        let wallets = Arc::clone(&WALLETS_STATE);
+        let mut locked = wallets.lock().await;
+        locked.insert(
+            address.to_string(),
+            Wallet::new(address.to_string(), addr_type),
+        );
+    }
+
+    #[tokio::test]
+    async fn get_wallets_returns_seeded_state() {
+        let _state = setup("get-wallets").await;
+        seed("wallet-control-test-1", AddressType::SUB).await;
+
+        let response = router()
+            .oneshot(
+                Request::builder()
+                    .uri("/wallets")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let wallets: HashMap<String, Wallet> = serde_json::from_slice(&body).unwrap();
+        assert!(wallets.contains_key("wallet-control-test-1"));
+    }
+
+    #[tokio::test]
+    async fn assign_then_heartbeat_moves_wallet_to_deployed() {
+        let _state = setup("assign-heartbeat").await;
+        seed("wallet-control-test-2", AddressType::SUB).await;
+
+        let body = serde_json::to_vec(&serde_json::json!({
+            "address": "wallet-control-test-2",
+            "deploy": {
+                "DEPLOYING": {
+                    "orderid": 1,
+                    "serverid": 1,
+                    "sshaddr": null,
+                    "sshport": null
+                }
+            }
+        }))
+        .unwrap();
+
+        let response = router()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/assign")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/heartbeat/wallet-control-test-2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let wallets = Arc::clone(&WALLETS_STATE);
+        let locked = wallets.lock().await;
+        let wallet = locked.get("wallet-control-test-2").unwrap();
+        match &wallet.deploy {
+            WalletDeployed::DEPLOYED { orderid, .. } => assert_eq!(*orderid, 1),
+            other => panic!("expected wallet to be DEPLOYED, got {:?}", other),
+        }
+        assert!(wallet.report_last_time.is_some());
+    }
+
+    #[tokio::test]
+    async fn heartbeat_on_not_deploying_wallet_returns_conflict() {
+        let _state = setup("heartbeat-conflict").await;
+        seed("wallet-control-test-3", AddressType::SUB).await;
+
+        let response = router()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/heartbeat/wallet-control-test-3")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    // post_cancel 直接转发给 Clore::cancel_order，会对真实的 Clore API 发起一次带副作用的取消请求，
+    // 不能在默认的 cargo test 里无条件跑（无网络环境会挂起，有网络/凭据环境会真的取消订单）。
+    // 默认忽略，只有显式 `cargo test -- --ignored` 并配置好沙箱/测试账号凭据时才执行。
+    #[tokio::test]
+    #[ignore = "hits the live Clore API (cancel_order); run explicitly with --ignored against a test account"]
+    async fn cancel_route_is_wired_to_clore() {
+        let _state = setup("cancel").await;
+
+        let response = router()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/cancel/42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_ne!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    // 不触网的路由匹配校验：未知方法/路径应当落到框架的 404/405，
+    // 间接确认 /cancel/:orderid 确实注册在路由表里而不是被遗漏。
+    #[tokio::test]
+    async fn cancel_route_rejects_wrong_method() {
+        let _state = setup("cancel-route-shape").await;
+
+        let response = router()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/cancel/42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod address;
+pub mod clore;
+pub mod control;
+pub mod ssh;
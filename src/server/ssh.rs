@@ -0,0 +1,10 @@
+use super::{address::Deployed, clore::Order};
+
+// 通过 SSH 登陆到已下单的服务器采集部署状态，供 Address::filter 用来回填 Deployed
+pub struct Ssh;
+
+impl Ssh {
+    pub async fn try_run_command_remote(_orders: &Vec<Order>) -> (Vec<(String, Deployed)>, String) {
+        (Vec::new(), String::new())
+    }
+}